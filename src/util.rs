@@ -1,13 +1,17 @@
 use crate::error::{Result, Wrap};
 use crate::ffi::{
-    unqlite_util_load_mmaped_file, unqlite_util_random_num, unqlite_util_random_string,
-    unqlite_util_release_mmaped_file,
+    unqlite_kv_append, unqlite_kv_store, unqlite_util_random_num, unqlite_util_random_string,
 };
 use crate::UnQLite;
-use std::ffi::CString;
-use std::mem;
-use std::os::raw::c_void;
+use std::fs::File;
+use std::io;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::{c_char, c_void};
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
+use std::ptr;
+use std::slice;
 
 /// Utility interfaces.
 pub trait Util {
@@ -16,56 +20,331 @@ pub trait Util {
     /// It will generate a english alphabet based string of length buf_size (last argument).
     fn random_string(&self, buf_size: u32) -> Vec<u8>;
 
+    /// Fill `buf` in place with a random english-alphabet string from the
+    /// UnQLite PRNG, returning the number of bytes written.
+    ///
+    /// Unlike `random_string`, this writes directly into the caller's buffer
+    /// instead of allocating, so it can be reused across hot-loop calls.
+    /// Buffers longer than `u32::MAX` are truncated; the return value always
+    /// reflects the number of bytes actually written.
+    fn random_string_into(&self, buf: &mut [u8]) -> usize;
+
+    /// Fill possibly-uninitialized `buf` in place with random bytes spanning
+    /// the full `0..=255` range, returning the number of bytes written.
+    ///
+    /// Unlike `random_string_into`, this draws from `random_num` rather than
+    /// the fixed english-alphabet string generator.
+    fn random_bytes_into(&self, buf: &mut [MaybeUninit<u8>]) -> usize;
+
     /// Generate random number using the UnQLite PRNG.
     ///
     /// It will return a 32-bit unsigned integer between 0 and 0xFFFFFFFF.
     fn random_num(&self) -> u32;
+
+    /// Generate a random number in `[0, bound)` using the UnQLite PRNG.
+    ///
+    /// Uses rejection sampling, discarding draws that fall in the final
+    /// partial interval of the `u32` range, so the result is unbiased.
+    /// Returns `0` when `bound == 0`.
+    fn random_num_below(&self, bound: u32) -> u32;
+
+    /// Memory-map `path` and store its contents under `key`, without first
+    /// copying the whole file into a heap `Vec`.
+    ///
+    /// The file is written in fixed-size chunks through the KV store/append
+    /// FFI so that memory usage stays bounded regardless of file size.
+    fn store_mmaped_file<P: AsRef<Path>>(&self, key: &[u8], path: P) -> Result<()>;
 }
 
+/// Size of the chunks `store_mmaped_file` feeds to the KV store/append FFI.
+const MMAP_STORE_CHUNK_SIZE: usize = 64 * 1024;
+
 impl Util for UnQLite {
     fn random_string(&self, buf_size: u32) -> Vec<u8> {
+        let mut buf = vec![0u8; buf_size as usize];
+        let written = self.random_string_into(&mut buf);
+        buf.truncate(written);
+        buf
+    }
+
+    fn random_string_into(&self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(u32::MAX as usize);
         unsafe {
-            let vec: Vec<u8> = Vec::with_capacity(buf_size as usize);
-            let z_buf = CString::new(vec).unwrap().into_raw();
-            unqlite_util_random_string(self.as_raw_mut_ptr(), z_buf, buf_size)
-                .wrap()
-                .unwrap();
-            Vec::from_raw_parts(z_buf as *mut u8, buf_size as usize, buf_size as usize)
+            unqlite_util_random_string(
+                self.as_raw_mut_ptr(),
+                buf.as_mut_ptr() as *mut c_char,
+                len as u32,
+            )
+            .wrap()
+            .unwrap();
         }
+        len
+    }
+
+    fn random_bytes_into(&self, buf: &mut [MaybeUninit<u8>]) -> usize {
+        for chunk in buf.chunks_mut(4) {
+            let word = self.random_num().to_ne_bytes();
+            for (dst, src) in chunk.iter_mut().zip(word.iter()) {
+                dst.write(*src);
+            }
+        }
+        buf.len()
     }
 
     fn random_num(&self) -> u32 {
         unsafe { unqlite_util_random_num(self.as_raw_mut_ptr()) }
     }
+
+    fn random_num_below(&self, bound: u32) -> u32 {
+        if bound == 0 {
+            return 0;
+        }
+
+        let limit = u32::MAX - (u32::MAX % bound);
+        loop {
+            let n = self.random_num();
+            if n < limit {
+                return n % bound;
+            }
+        }
+    }
+
+    fn store_mmaped_file<P: AsRef<Path>>(&self, key: &[u8], path: P) -> Result<()> {
+        let mmap = load_mmaped_file(path)?;
+        let mut chunks = mmap.as_slice().chunks(MMAP_STORE_CHUNK_SIZE);
+
+        let first = chunks.next().unwrap_or(&[]);
+        unsafe {
+            unqlite_kv_store(
+                self.as_raw_mut_ptr(),
+                key.as_ptr() as *const c_void,
+                key.len() as i32,
+                first.as_ptr() as *const c_void,
+                first.len() as i64,
+            )
+            .wrap()?;
+        }
+
+        for chunk in chunks {
+            unsafe {
+                unqlite_kv_append(
+                    self.as_raw_mut_ptr(),
+                    key.as_ptr() as *const c_void,
+                    key.len() as i32,
+                    chunk.as_ptr() as *const c_void,
+                    chunk.len() as i64,
+                )
+                .wrap()?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-/// Load memory-mapped file so that we can save it to UnQLite
+/// Load memory-mapped file so that we can save it to UnQLite.
 ///
-/// NOTE: DONOT USE: will throw unimplemented error.
+/// Maps the file read-only for its full length and hands it back as an
+/// `Mmap` that can be read as a plain `&[u8]`.
 pub fn load_mmaped_file<P: AsRef<Path>>(path: P) -> Result<Mmap> {
+    let file = File::open(path.as_ref())?;
+    let size = file.metadata()?.len() as usize;
+
+    if size == 0 {
+        return Ok(Mmap {
+            ptr: ptr::null_mut(),
+            size: 0,
+        });
+    }
+
     unsafe {
-        let path = path.as_ref();
-        let mut ptr: *mut c_void = mem::MaybeUninit::uninit().assume_init();
-        let mut size: i64 = 0;
-        let cpath = CString::new(path.to_str().expect("cannot convert the path to str"))?;
-        unqlite_util_load_mmaped_file(cpath.as_ptr(), &mut ptr, &mut size)
-            .wrap()
-            .map(|_| Mmap {
-                ptr: ptr,
-                size: size,
-            })
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Mmap { ptr, size })
     }
 }
 
 /// UnQLite hosted memory mapped file
 pub struct Mmap {
-    pub ptr: *mut c_void,
-    pub size: i64,
+    ptr: *mut libc::c_void,
+    size: usize,
+}
+
+impl Mmap {
+    /// Returns the mapped file contents as a byte slice.
+    pub fn as_slice(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.size) }
+        }
+    }
+
+    /// Returns the length in bytes of the mapped file.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if the mapped file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
 }
 
 impl Drop for Mmap {
     fn drop(&mut self) {
-        let _ = wrap!(util_release_mmaped_file, self.ptr, self.size);
+        if !self.ptr.is_null() {
+            unsafe {
+                libc::munmap(self.ptr, self.size);
+            }
+        }
+    }
+}
+
+/// Anonymous, writable memory mapping, not backed by any file.
+///
+/// Useful for building a scratch buffer whose bytes, read as `&[u8]`, can
+/// later be written to the KV store. It has no backing file, so it cannot be
+/// passed to `store_mmaped_file`, which maps a file by path.
+pub struct MmapMut {
+    ptr: *mut libc::c_void,
+    size: usize,
+}
+
+impl MmapMut {
+    /// Create an anonymous, writable mapping of `size` bytes.
+    pub fn anonymous(size: usize) -> Result<MmapMut> {
+        if size == 0 {
+            return Ok(MmapMut {
+                ptr: ptr::null_mut(),
+                size: 0,
+            });
+        }
+
+        unsafe {
+            let ptr = libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_ANON | libc::MAP_PRIVATE,
+                -1,
+                0,
+            );
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(MmapMut { ptr, size })
+        }
+    }
+
+    /// Returns the mapping contents as a byte slice.
+    pub fn as_slice(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.size) }
+        }
+    }
+
+    /// Returns the mapping as a mutable byte slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.ptr.is_null() {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.ptr as *mut u8, self.size) }
+        }
+    }
+
+    /// Returns the length in bytes of the mapping.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if the mapping is empty.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl Deref for MmapMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for MmapMut {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+impl Drop for MmapMut {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                libc::munmap(self.ptr, self.size);
+            }
+        }
+    }
+}
+
+/// Adapts the UnQLite PRNG to the `rand` ecosystem.
+///
+/// Wraps a `&UnQLite` and draws all randomness from `random_num`.
+#[cfg(feature = "rand")]
+pub struct UnQLiteRng<'a> {
+    db: &'a UnQLite,
+}
+
+#[cfg(feature = "rand")]
+impl<'a> UnQLiteRng<'a> {
+    /// Wrap `db` as a `rand::RngCore` source.
+    pub fn new(db: &'a UnQLite) -> Self {
+        UnQLiteRng { db }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<'a> rand::RngCore for UnQLiteRng<'a> {
+    fn next_u32(&mut self) -> u32 {
+        self.db.random_num()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let hi = u64::from(self.next_u32());
+        let lo = u64::from(self.next_u32());
+        (hi << 32) | lo
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let word = self.next_u32().to_ne_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
     }
 }
 
@@ -81,11 +360,45 @@ mod tests {
         let _ = unqlite.random_string(32);
     }
 
+    #[test]
+    fn test_random_string_into() {
+        let unqlite = UnQLite::create_in_memory();
+        let mut buf = [0u8; 32];
+        let written = unqlite.random_string_into(&mut buf);
+        assert_eq!(written, 32);
+    }
+
+    #[test]
+    fn test_random_bytes_into() {
+        let unqlite = UnQLite::create_in_memory();
+        let mut buf = [MaybeUninit::uninit(); 32];
+        let written = unqlite.random_bytes_into(&mut buf);
+        assert_eq!(written, 32);
+    }
+
     #[test]
     fn test_random_num() {
         let _ = UnQLite::create_in_memory().random_num();
     }
 
+    #[test]
+    fn test_random_num_below() {
+        let unqlite = UnQLite::create_in_memory();
+        assert_eq!(unqlite.random_num_below(0), 0);
+        for _ in 0..100 {
+            assert!(unqlite.random_num_below(10) < 10);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_anonymous_mmap() {
+        let mut mmap = MmapMut::anonymous(16).unwrap();
+        assert_eq!(mmap.len(), 16);
+        mmap.as_mut_slice().copy_from_slice(&[1u8; 16]);
+        assert_eq!(mmap.as_slice(), &[1u8; 16]);
+    }
+
     #[test]
     #[cfg(feature = "mmap")]
     fn test_mmap() {
@@ -94,6 +407,33 @@ mod tests {
         let mut f = NamedTempFile::new().expect("get named temp file");
         let _ = f.write_all(b"Hello, world!");
         let _ = f.sync_all();
-        load_mmaped_file(f.path()).unwrap();
+        let mmap = load_mmaped_file(f.path()).unwrap();
+        assert_eq!(mmap.as_slice(), b"Hello, world!");
+        assert_eq!(mmap.len(), 13);
+        assert!(!mmap.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_store_mmaped_file() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+        let mut f = NamedTempFile::new().expect("get named temp file");
+        let _ = f.write_all(b"Hello, world!");
+        let _ = f.sync_all();
+        let unqlite = UnQLite::create_in_memory();
+        unqlite.store_mmaped_file(b"greeting", f.path()).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_unqlite_rng() {
+        use rand::RngCore;
+        let unqlite = UnQLite::create_in_memory();
+        let mut rng = UnQLiteRng::new(&unqlite);
+        let _ = rng.next_u32();
+        let _ = rng.next_u64();
+        let mut buf = [0u8; 16];
+        rng.fill_bytes(&mut buf);
     }
 }